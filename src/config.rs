@@ -0,0 +1,93 @@
+//! Per-rule severity configuration, so that a new or noisy rule can be staged in across a huge
+//! tree (like Nixpkgs) instead of immediately failing every run.
+//!
+//! Rules are identified by the same stable [`NixpkgsProblem::rule_id`](crate::nixpkgs_problem::NixpkgsProblem::rule_id)
+//! used for the structured output formats. A rule with no configured severity defaults to
+//! [`Severity::Error`].
+
+use crate::nixpkgs_problem::NixpkgsProblem;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// How a rule's violations should be treated once found.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Severity {
+    /// Fails the overall run.
+    #[default]
+    Error,
+    /// Printed separately, but doesn't fail the run.
+    Warn,
+    /// Not reported at all.
+    Off,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "warn" => Ok(Self::Warn),
+            "off" => Ok(Self::Off),
+            other => Err(format!("invalid severity \"{other}\", must be one of error, warn, off")),
+        }
+    }
+}
+
+// `.nixpkgs-vet.toml` and `--severity` share the same set of valid strings, so the TOML
+// representation is defined in terms of `FromStr` rather than duplicating it via `rename_all`.
+impl<'de> serde::Deserialize<'de> for Severity {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// The `.nixpkgs-vet.toml` config file: a severity per rule ID, overriding the default of
+/// [`Severity::Error`] for any rule not mentioned.
+#[derive(Default, Deserialize)]
+pub struct Config {
+    #[serde(default, rename = "rules")]
+    severities: HashMap<String, Severity>,
+}
+
+impl Config {
+    pub fn from_toml_str(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// Applies a single `--severity <ruleId>=<level>` CLI override, as repeatable flags take
+    /// precedence over the config file.
+    pub fn set_severity(&mut self, rule_id: impl Into<String>, severity: Severity) {
+        self.severities.insert(rule_id.into(), severity);
+    }
+
+    pub fn severity_for(&self, rule_id: &str) -> Severity {
+        self.severities.get(rule_id).copied().unwrap_or_default()
+    }
+}
+
+/// `problems` partitioned by their configured severity. `off`-level problems are dropped
+/// entirely; the run should fail if and only if `errors` is non-empty.
+#[derive(Default)]
+pub struct PartitionedProblems {
+    pub errors: Vec<NixpkgsProblem>,
+    pub warnings: Vec<NixpkgsProblem>,
+}
+
+/// Partitions `problems` by the severity `config` assigns to each one's rule.
+pub fn partition(problems: Vec<NixpkgsProblem>, config: &Config) -> PartitionedProblems {
+    let mut partitioned = PartitionedProblems::default();
+    for problem in problems {
+        match config.severity_for(problem.rule_id()) {
+            Severity::Error => partitioned.errors.push(problem),
+            Severity::Warn => partitioned.warnings.push(problem),
+            Severity::Off => {}
+        }
+    }
+    partitioned
+}
@@ -0,0 +1,161 @@
+//! Mechanical fixes for the subset of [`NixpkgsProblem`](crate::nixpkgs_problem::NixpkgsProblem)
+//! variants that carry enough information to describe their own correction.
+//!
+//! A [`FixPlan`] only *describes* the fix (file moves plus text edits anchored by line/column)
+//! so that it can be constructed without touching the filesystem, and therefore tested in
+//! isolation. Actually applying or previewing a plan requires reading the affected files, since
+//! [`TextEdit`] positions have to be resolved against the file's current contents.
+
+use crate::line_index::LineIndex;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// A mechanical correction for a single [`NixpkgsProblem`](crate::nixpkgs_problem::NixpkgsProblem).
+#[derive(Default)]
+pub struct FixPlan {
+    pub moves: Vec<FileMove>,
+    pub edits: Vec<TextEdit>,
+}
+
+impl FixPlan {
+    pub fn moves(moves: Vec<FileMove>) -> Self {
+        Self { moves, edits: Vec::new() }
+    }
+
+    pub fn edits(edits: Vec<TextEdit>) -> Self {
+        Self { moves: Vec::new(), edits }
+    }
+
+    /// Renders a `--fix --dry-run` preview: one line per move, and a unified-diff-style
+    /// before/after pair per edit. `read_source` is used to resolve each edit's byte range and
+    /// to show the line it's replacing.
+    pub fn preview(&self, read_source: impl Fn(&Path) -> io::Result<String>) -> io::Result<String> {
+        let mut out = String::new();
+        for file_move in &self.moves {
+            out.push_str(&format!("move {} -> {}\n", file_move.from.display(), file_move.to.display()));
+        }
+        for edit in &self.edits {
+            let source = read_source(&edit.file)?;
+            let index = LineIndex::new(&source);
+            let before = index.line_text(&source, edit.line).to_owned();
+            let after = index.line_text(&edit.apply(&source), edit.line).to_owned();
+            out.push_str(&format!("{}:{}:{}\n", edit.file.display(), edit.line, edit.column));
+            out.push_str(&format!("- {before}\n"));
+            out.push_str(&format!("+ {after}\n"));
+        }
+        Ok(out)
+    }
+}
+
+/// Moves a package directory or file from one path to another, relative to the Nixpkgs root.
+pub struct FileMove {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// A single-span replacement in a source file.
+///
+/// The position is stored as 1-indexed `(line, column)` rather than a byte range, because
+/// computing a byte range requires the file's current contents, which a [`FixPlan`] is
+/// deliberately built without reading. Call [`TextEdit::resolve`] once you have those contents.
+pub struct TextEdit {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub replacement: String,
+}
+
+impl TextEdit {
+    /// Resolves this edit against `source` (the current contents of [`Self::file`]) into a
+    /// concrete byte range, suitable for a simple string splice.
+    ///
+    /// The span's length isn't stored on `TextEdit` itself: it's the length of the token
+    /// actually present in `source` at `(line, column)`, not a length computed ahead of time
+    /// from some regenerated replacement text, since those can disagree (e.g. a `../foo` in the
+    /// source vs. a `./foo`-normalized suggestion of different length).
+    pub fn resolve(&self, source: &str) -> Range<usize> {
+        let index = LineIndex::new(source);
+        let start = index.from_line_column(source, self.line, self.column);
+        let token_len = index.token_len(source, self.line, self.column);
+        let end = index.from_line_column(source, self.line, self.column + token_len);
+        start..end
+    }
+
+    /// Applies this edit to `source`, returning the new file contents.
+    pub fn apply(&self, source: &str) -> String {
+        let range = self.resolve(source);
+        format!("{}{}{}", &source[..range.start], self.replacement, &source[range.end..])
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_edit_splices_in_the_replacement() {
+        let source = "foo = callPackage ../wrong/path { };\n";
+        let edit = TextEdit {
+            file: PathBuf::from("pkgs/top-level/all-packages.nix"),
+            line: 1,
+            column: 19, // the '.' starting "../wrong/path"
+            replacement: "./by-name/fo/foo".to_owned(),
+        };
+        assert_eq!(edit.apply(source), "foo = callPackage ./by-name/fo/foo { };\n");
+    }
+
+    #[test]
+    fn text_edit_handles_multibyte_source() {
+        // The token to replace starts after a multi-byte char earlier on the line; resolving
+        // by char column (not byte offset) is what keeps the splice from landing mid-character.
+        let source = "héllo = ./wörld { };\n";
+        let edit = TextEdit {
+            file: PathBuf::from("dummy.nix"),
+            line: 1,
+            column: 9, // the '.' starting "./wörld"
+            replacement: "./nëw".to_owned(),
+        };
+        assert_eq!(edit.apply(source), "héllo = ./nëw { };\n");
+    }
+
+    #[test]
+    fn text_edit_replaces_whole_token_regardless_of_replacement_length() {
+        // A shorter source token than the replacement (or vice versa) must not corrupt the
+        // surrounding text: the span is sized from the source, not from either string's length.
+        let source = "foo = callPackage ./a { };\n";
+        let edit = TextEdit {
+            file: PathBuf::from("dummy.nix"),
+            line: 1,
+            column: 19, // the '.' starting "./a"
+            replacement: "./much/longer/path".to_owned(),
+        };
+        assert_eq!(edit.apply(source), "foo = callPackage ./much/longer/path { };\n");
+    }
+
+    #[test]
+    fn fix_plan_preview_renders_moves_and_edits() {
+        let plan = FixPlan {
+            moves: vec![FileMove {
+                from: PathBuf::from("pkgs/applications/foo/package.nix"),
+                to: PathBuf::from("pkgs/by-name/fo/foo/package.nix"),
+            }],
+            edits: vec![TextEdit {
+                file: PathBuf::from("pkgs/top-level/all-packages.nix"),
+                line: 1,
+                column: 19,
+                replacement: "./by-name/fo/foo".to_owned(),
+            }],
+        };
+        let preview = plan
+            .preview(|_| Ok("foo = callPackage ../wrong/path { };\n".to_owned()))
+            .unwrap();
+        assert_eq!(
+            preview,
+            "move pkgs/applications/foo/package.nix -> pkgs/by-name/fo/foo/package.nix\n\
+             pkgs/top-level/all-packages.nix:1:19\n\
+             - foo = callPackage ../wrong/path { };\n\
+             + foo = callPackage ./by-name/fo/foo { };\n"
+        );
+    }
+}
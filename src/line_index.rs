@@ -0,0 +1,136 @@
+//! Mapping between byte offsets and 1-indexed `(line, column)` positions in a source file,
+//! and rendering rustc-style code frames from such positions.
+//!
+//! Columns are counted in chars, not bytes, matching what `builtins.unsafeGetAttrPos` reports.
+
+/// Precomputed byte offsets of the start of each line in a source file.
+pub struct LineIndex {
+    /// `line_starts[i]` is the byte offset of the start of line `i + 1`.
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(offset, _)| offset + 1));
+        Self { line_starts }
+    }
+
+    /// Returns the text of the given 1-indexed `line`, without its trailing newline.
+    pub fn line_text<'a>(&self, source: &'a str, line: usize) -> &'a str {
+        let start = self.line_starts[line - 1];
+        let end = self.line_starts.get(line).copied().unwrap_or(source.len());
+        source[start..end].trim_end_matches('\n')
+    }
+
+    /// The inverse of whatever produced a `(line, column)` position: maps it back to a byte
+    /// offset into `source`.
+    ///
+    /// If `column` points past the end of the line (e.g. exactly at the newline), this
+    /// resolves to the end of that line, i.e. the position underlines the end of the line
+    /// rather than panicking or wrapping onto the next one.
+    pub fn from_line_column(&self, source: &str, line: usize, column: usize) -> usize {
+        let line_start = self.line_starts[line - 1];
+        let line_text = self.line_text(source, line);
+        let char_offset = line_text
+            .char_indices()
+            .nth(column - 1)
+            .map_or(line_text.len(), |(byte_offset, _)| byte_offset);
+        line_start + char_offset
+    }
+
+    /// The length, in chars, of the token starting at `column` on `line`: everything up to the
+    /// next whitespace, or a single char if `column` already points at whitespace (or past the
+    /// end of the line). Nix path expressions (`./foo`, `../bar/baz.nix`) are bare, space-free
+    /// tokens, so this is enough to find their extent without parsing the file.
+    pub fn token_len(&self, source: &str, line: usize, column: usize) -> usize {
+        self.line_text(source, line)
+            .chars()
+            .skip(column - 1)
+            .take_while(|c| !c.is_whitespace())
+            .count()
+            .max(1)
+    }
+}
+
+/// Renders a single rustc-style code frame: the source line at `line`, prefixed with a
+/// `<line> | ` gutter, followed by a `^^^` underline beneath the char starting at `column`.
+pub fn code_frame(source: &str, line: usize, column: usize) -> String {
+    let index = LineIndex::new(source);
+    let line_text = index.line_text(source, line);
+
+    let gutter = format!("{line} | ");
+    let mut frame = format!("{gutter}{line_text}");
+
+    let caret_len = index.token_len(source, line, column);
+    let padding = " ".repeat(gutter.chars().count() + column - 1);
+
+    frame.push('\n');
+    frame.push_str(&padding);
+    frame.push_str(&"^".repeat(caret_len));
+    frame
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_line_column_is_one_indexed() {
+        let source = "foo\nbar\n";
+        let index = LineIndex::new(source);
+        // Column 1 on line 1 is the 'f' of "foo", at byte offset 0.
+        assert_eq!(index.from_line_column(source, 1, 1), 0);
+        // Column 1 on line 2 is the 'b' of "bar", right after the first newline.
+        assert_eq!(index.from_line_column(source, 2, 1), 4);
+    }
+
+    #[test]
+    fn from_line_column_at_newline_resolves_to_end_of_prior_line() {
+        let source = "foo\nbar\n";
+        let index = LineIndex::new(source);
+        // Column 4 on line 1 is one past the last char of "foo", i.e. the newline itself.
+        assert_eq!(index.from_line_column(source, 1, 4), 3);
+        // Columns further past the end clamp the same way, rather than wrapping onto line 2.
+        assert_eq!(index.from_line_column(source, 1, 100), 3);
+    }
+
+    #[test]
+    fn from_line_column_counts_chars_not_bytes() {
+        // "héllo" has 5 chars but 6 bytes, since 'é' is 2 bytes in UTF-8.
+        let source = "héllo wörld\n";
+        let index = LineIndex::new(source);
+        // Column 7 is the 'w' of "wörld", which starts after "héllo " (5 chars + 1 space).
+        assert_eq!(index.from_line_column(source, 1, 7), "héllo ".len());
+    }
+
+    #[test]
+    fn token_len_stops_at_whitespace() {
+        let source = "foo = ./bar/baz.nix { };\n";
+        let index = LineIndex::new(source);
+        // Column 7 is the '.' starting the path expression.
+        assert_eq!(index.token_len(source, 1, 7), "./bar/baz.nix".chars().count());
+    }
+
+    #[test]
+    fn token_len_is_at_least_one_past_end_of_line() {
+        let source = "foo\n";
+        let index = LineIndex::new(source);
+        assert_eq!(index.token_len(source, 1, 100), 1);
+    }
+
+    #[test]
+    fn code_frame_aligns_caret_under_ascii_token() {
+        let source = "foo = ./bar { };\n";
+        let frame = code_frame(source, 1, 7);
+        assert_eq!(frame, "1 | foo = ./bar { };\n          ^^^^^");
+    }
+
+    #[test]
+    fn code_frame_aligns_caret_under_utf8_token() {
+        // The caret row must be padded by char count, not byte count, or it would drift right
+        // of the intended column whenever a multi-byte char appears before it.
+        let source = "héllo = ./wörld { };\n";
+        let frame = code_frame(source, 1, 9);
+        assert_eq!(frame, "1 | héllo = ./wörld { };\n            ^^^^^^^");
+    }
+}
@@ -1,3 +1,5 @@
+use crate::fix::{FileMove, FixPlan, TextEdit};
+use crate::output::Location;
 use crate::structure;
 use crate::utils::PACKAGE_NIX_FILENAME;
 use indoc::writedoc;
@@ -43,15 +45,28 @@ pub enum NixpkgsProblem {
         relative_package_file: PathBuf,
         package_name: String,
     },
-    WrongCallPackage {
+    NonCallPackage {
         relative_package_file: PathBuf,
         package_name: String,
     },
+    NonTopLevelCallPackage {
+        package_name: String,
+        file: PathBuf,
+        line: usize,
+        column: usize,
+        receiver: String,
+    },
     WrongCallPackagePath {
         package_name: String,
         file: PathBuf,
         line: usize,
         column: usize,
+        /// The line/column of the `callPackage` path *argument* itself (e.g. `./wrong` in
+        /// `foo = callPackage ./wrong { };`), as opposed to `line`/`column` above which is the
+        /// position of the `foo` attribute as reported by `builtins.unsafeGetAttrPos`. This is
+        /// what `--fix` needs to know which span of the file to rewrite.
+        path_line: usize,
+        path_column: usize,
         actual_path: PathBuf,
         expected_path: PathBuf,
     },
@@ -79,24 +94,28 @@ pub enum NixpkgsProblem {
         relative_package_dir: PathBuf,
         subpath: PathBuf,
         line: usize,
+        column: usize,
         text: String,
     },
     SearchPath {
         relative_package_dir: PathBuf,
         subpath: PathBuf,
         line: usize,
+        column: usize,
         text: String,
     },
     OutsidePathReference {
         relative_package_dir: PathBuf,
         subpath: PathBuf,
         line: usize,
+        column: usize,
         text: String,
     },
     UnresolvablePathReference {
         relative_package_dir: PathBuf,
         subpath: PathBuf,
         line: usize,
+        column: usize,
         text: String,
         io_error: io::Error,
     },
@@ -176,17 +195,23 @@ impl fmt::Display for NixpkgsProblem {
                     "pkgs.{package_name}: This attribute is not defined but it should be defined automatically as {}",
                     relative_package_file.display()
                 ),
-            NixpkgsProblem::WrongCallPackage { relative_package_file, package_name } =>
+            NixpkgsProblem::NonCallPackage { relative_package_file, package_name } =>
                 write!(
                     f,
-                    "pkgs.{package_name}: This attribute is manually defined (most likely in pkgs/top-level/all-packages.nix), which is only allowed if the definition is of the form `pkgs.callPackage {} {{ ... }}` with a non-empty second argument.",
+                    "pkgs.{package_name}: This attribute is manually defined (most likely in pkgs/top-level/all-packages.nix), but its definition doesn't call `callPackage` at all. It must be defined like `pkgs.callPackage {} {{ ... }}` with a non-empty second argument.",
                     relative_package_file.display()
                 ),
-            NixpkgsProblem::WrongCallPackagePath { package_name, file, line, column, actual_path, expected_path } =>
+            NixpkgsProblem::NonTopLevelCallPackage { package_name, file, line, column, receiver } =>
+                write!(
+                    f,
+                    "pkgs.{package_name}: This attribute is manually defined (most likely in pkgs/top-level/all-packages.nix) in {}:{line}:{column} as `{receiver}.callPackage {{ ... }}`, but only `pkgs.callPackage`/bare `callPackage` at the top level is allowed.",
+                    file.display(),
+                ),
+            NixpkgsProblem::WrongCallPackagePath { package_name, file, line, column, actual_path, expected_path, .. } =>
                 writedoc! {
                     f,
                     "
-                    - Because {} exists, the attribute `pkgs.{package_name}` must be defined like
+                    Because {} exists, the attribute `pkgs.{package_name}` must be defined like
 
                         {package_name} = callPackage {} {{ /* ... */ }};
 
@@ -217,7 +242,7 @@ impl fmt::Display for NixpkgsProblem {
                 writedoc!(
                     f,
                     "
-                    - Because {} exists, the attribute `pkgs.{package_name}` must be defined like
+                    Because {} exists, the attribute `pkgs.{package_name}` must be defined like
 
                         {package_name} = callPackage {} {{ /* ... */ }};
 
@@ -252,34 +277,34 @@ impl fmt::Display for NixpkgsProblem {
                     relative_package_dir.display(),
                     subpath.display(),
                 ),
-            NixpkgsProblem::PathInterpolation { relative_package_dir, subpath, line, text } =>
+            NixpkgsProblem::PathInterpolation { relative_package_dir, subpath, line, column, text } =>
                 write!(
                     f,
-                    "{}: File {} at line {line} contains the path expression \"{}\", which is not yet supported and may point outside the directory of that package.",
+                    "{}: File {} at {line}:{column} contains the path expression \"{}\", which is not yet supported and may point outside the directory of that package.",
                     relative_package_dir.display(),
                     subpath.display(),
                     text
                 ),
-            NixpkgsProblem::SearchPath { relative_package_dir, subpath, line, text } =>
+            NixpkgsProblem::SearchPath { relative_package_dir, subpath, line, column, text } =>
                 write!(
                     f,
-                    "{}: File {} at line {line} contains the nix search path expression \"{}\" which may point outside the directory of that package.",
+                    "{}: File {} at {line}:{column} contains the nix search path expression \"{}\" which may point outside the directory of that package.",
                     relative_package_dir.display(),
                     subpath.display(),
                     text
                 ),
-            NixpkgsProblem::OutsidePathReference { relative_package_dir, subpath, line, text } =>
+            NixpkgsProblem::OutsidePathReference { relative_package_dir, subpath, line, column, text } =>
                 write!(
                     f,
-                    "{}: File {} at line {line} contains the path expression \"{}\" which may point outside the directory of that package.",
+                    "{}: File {} at {line}:{column} contains the path expression \"{}\" which may point outside the directory of that package.",
                     relative_package_dir.display(),
                     subpath.display(),
                     text,
                 ),
-            NixpkgsProblem::UnresolvablePathReference { relative_package_dir, subpath, line, text, io_error } =>
+            NixpkgsProblem::UnresolvablePathReference { relative_package_dir, subpath, line, column, text, io_error } =>
                 write!(
                     f,
-                    "{}: File {} at line {line} contains the path expression \"{}\" which cannot be resolved: {io_error}.",
+                    "{}: File {} at {line}:{column} contains the path expression \"{}\" which cannot be resolved: {io_error}.",
                     relative_package_dir.display(),
                     subpath.display(),
                     text,
@@ -340,6 +365,152 @@ impl fmt::Display for NixpkgsProblem {
     }
 }
 
+impl NixpkgsProblem {
+    /// A stable identifier for this kind of problem, unrelated to the `Display` text.
+    ///
+    /// This is used as the `ruleId` in structured output formats (JSON/SARIF) and as the
+    /// key for per-rule severity configuration (see `crate::config`). It must stay stable across
+    /// releases even if the `Display` message changes.
+    pub fn rule_id(&self) -> &'static str {
+        match self {
+            NixpkgsProblem::ShardNonDir { .. } => "ShardNonDir",
+            NixpkgsProblem::InvalidShardName { .. } => "InvalidShardName",
+            NixpkgsProblem::PackageNonDir { .. } => "PackageNonDir",
+            NixpkgsProblem::CaseSensitiveDuplicate { .. } => "CaseSensitiveDuplicate",
+            NixpkgsProblem::InvalidPackageName { .. } => "InvalidPackageName",
+            NixpkgsProblem::IncorrectShard { .. } => "IncorrectShard",
+            NixpkgsProblem::PackageNixNonExistent { .. } => "PackageNixNonExistent",
+            NixpkgsProblem::PackageNixDir { .. } => "PackageNixDir",
+            NixpkgsProblem::UndefinedAttr { .. } => "UndefinedAttr",
+            NixpkgsProblem::NonCallPackage { .. } => "NonCallPackage",
+            NixpkgsProblem::NonTopLevelCallPackage { .. } => "NonTopLevelCallPackage",
+            NixpkgsProblem::WrongCallPackagePath { .. } => "WrongCallPackagePath",
+            NixpkgsProblem::NonSyntacticCallPackage { .. } => "NonSyntacticCallPackage",
+            NixpkgsProblem::NonDerivation { .. } => "NonDerivation",
+            NixpkgsProblem::OutsideSymlink { .. } => "OutsideSymlink",
+            NixpkgsProblem::UnresolvableSymlink { .. } => "UnresolvableSymlink",
+            NixpkgsProblem::PathInterpolation { .. } => "PathInterpolation",
+            NixpkgsProblem::SearchPath { .. } => "SearchPath",
+            NixpkgsProblem::OutsidePathReference { .. } => "OutsidePathReference",
+            NixpkgsProblem::UnresolvablePathReference { .. } => "UnresolvablePathReference",
+            NixpkgsProblem::MovedOutOfByName { .. } => "MovedOutOfByName",
+            NixpkgsProblem::NewPackageNotUsingByName { .. } => "NewPackageNotUsingByName",
+            NixpkgsProblem::InternalCallPackageUsed { .. } => "InternalCallPackageUsed",
+            NixpkgsProblem::CannotDetermineAttributeLocation { .. } => "CannotDetermineAttributeLocation",
+        }
+    }
+
+    /// Where in the Nixpkgs tree this problem was found, if it can be pinned to a specific
+    /// file (and, for some variants, a line/column within that file).
+    ///
+    /// This backs the `location` field of the structured output formats. Variants that can
+    /// only be attributed to an attribute name (e.g. `InternalCallPackageUsed`) return `None`.
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            NixpkgsProblem::ShardNonDir { relative_shard_path } =>
+                Some(Location::new(relative_shard_path.clone())),
+            NixpkgsProblem::InvalidShardName { relative_shard_path, .. } =>
+                Some(Location::new(relative_shard_path.clone())),
+            NixpkgsProblem::PackageNonDir { relative_package_dir } =>
+                Some(Location::new(relative_package_dir.clone())),
+            NixpkgsProblem::CaseSensitiveDuplicate { relative_shard_path, .. } =>
+                Some(Location::new(relative_shard_path.clone())),
+            NixpkgsProblem::InvalidPackageName { relative_package_dir, .. } =>
+                Some(Location::new(relative_package_dir.clone())),
+            NixpkgsProblem::IncorrectShard { relative_package_dir, .. } =>
+                Some(Location::new(relative_package_dir.clone())),
+            NixpkgsProblem::PackageNixNonExistent { relative_package_dir } =>
+                Some(Location::new(relative_package_dir.clone())),
+            NixpkgsProblem::PackageNixDir { relative_package_dir } =>
+                Some(Location::new(relative_package_dir.clone())),
+            NixpkgsProblem::UndefinedAttr { relative_package_file, .. } =>
+                Some(Location::new(relative_package_file.clone())),
+            NixpkgsProblem::NonCallPackage { relative_package_file, .. } =>
+                Some(Location::new(relative_package_file.clone())),
+            NixpkgsProblem::NonTopLevelCallPackage { file, line, column, .. } =>
+                Some(Location::new(file.clone()).with_region(*line, *column)),
+            NixpkgsProblem::WrongCallPackagePath { file, line, column, .. } =>
+                Some(Location::new(file.clone()).with_region(*line, *column)),
+            NixpkgsProblem::NonSyntacticCallPackage { file, line, column, .. } =>
+                Some(Location::new(file.clone()).with_region(*line, *column)),
+            NixpkgsProblem::NonDerivation { relative_package_file, .. } =>
+                Some(Location::new(relative_package_file.clone())),
+            NixpkgsProblem::OutsideSymlink { relative_package_dir, subpath } =>
+                Some(Location::new(relative_package_dir.join(subpath))),
+            NixpkgsProblem::UnresolvableSymlink { relative_package_dir, subpath, .. } =>
+                Some(Location::new(relative_package_dir.join(subpath))),
+            NixpkgsProblem::PathInterpolation { relative_package_dir, subpath, line, column, .. } =>
+                Some(Location::new(relative_package_dir.join(subpath)).with_region(*line, *column)),
+            NixpkgsProblem::SearchPath { relative_package_dir, subpath, line, column, .. } =>
+                Some(Location::new(relative_package_dir.join(subpath)).with_region(*line, *column)),
+            NixpkgsProblem::OutsidePathReference { relative_package_dir, subpath, line, column, .. } =>
+                Some(Location::new(relative_package_dir.join(subpath)).with_region(*line, *column)),
+            NixpkgsProblem::UnresolvablePathReference { relative_package_dir, subpath, line, column, .. } =>
+                Some(Location::new(relative_package_dir.join(subpath)).with_region(*line, *column)),
+            NixpkgsProblem::MovedOutOfByName { package_name, .. } =>
+                Some(Location::new(structure::relative_file_for_package(package_name))),
+            NixpkgsProblem::NewPackageNotUsingByName { package_name, .. } =>
+                Some(Location::new(structure::relative_file_for_package(package_name))),
+            NixpkgsProblem::InternalCallPackageUsed { .. } => None,
+            NixpkgsProblem::CannotDetermineAttributeLocation { .. } => None,
+        }
+    }
+
+    /// Renders a rustc-style code frame for this problem: the offending source line with a
+    /// `^^^` underline beneath the relevant span.
+    ///
+    /// `source` must be the full contents of the file this problem's [`Location`] points to.
+    /// Returns `None` if this problem doesn't carry column information (either because it has
+    /// no location at all, or because its location is only known down to the file).
+    pub fn code_frame(&self, source: &str) -> Option<String> {
+        let region = self.location()?.region?;
+        Some(crate::line_index::code_frame(source, region.start_line, region.start_column))
+    }
+
+    /// Describes the mechanical correction for this problem, if one can be determined from the
+    /// information already carried by the variant. Returns `None` for problems that don't have
+    /// a safe, deterministic fix (e.g. those requiring a human to decide where to put something).
+    pub fn fix_plan(&self) -> Option<FixPlan> {
+        match self {
+            NixpkgsProblem::IncorrectShard { relative_package_dir, correct_relative_package_dir } =>
+                Some(FixPlan::moves(vec![FileMove {
+                    from: relative_package_dir.clone(),
+                    to: correct_relative_package_dir.clone(),
+                }])),
+            NixpkgsProblem::WrongCallPackagePath { file, path_line, path_column, expected_path, .. } => {
+                let replacement = create_path_expr(file, expected_path);
+                Some(FixPlan::edits(vec![TextEdit {
+                    file: file.clone(),
+                    line: *path_line,
+                    column: *path_column,
+                    replacement,
+                }]))
+            }
+            NixpkgsProblem::MovedOutOfByName { package_name, call_package_path, .. } => {
+                // We know where the package needs to end up, but (unlike `WrongCallPackagePath`)
+                // this variant doesn't carry the position of the manual `callPackage` in e.g.
+                // `pkgs/top-level/all-packages.nix`, so we can only move the package file back;
+                // removing the now-unnecessary manual `callPackage` is left to the user.
+                let from = call_package_path.clone()?;
+                Some(FixPlan::moves(vec![FileMove {
+                    from,
+                    to: structure::relative_file_for_package(package_name),
+                }]))
+            }
+            NixpkgsProblem::NewPackageNotUsingByName { package_name, call_package_path, .. } => {
+                // Same caveat as `MovedOutOfByName`: we can move the package.nix into
+                // `pkgs/by-name`, but not rewrite or remove the manual `callPackage` call site.
+                let from = call_package_path.clone()?;
+                Some(FixPlan::moves(vec![FileMove {
+                    from,
+                    to: structure::relative_file_for_package(package_name),
+                }]))
+            }
+            _ => None,
+        }
+    }
+}
+
 /// Creates a Nix path expression that when put into Nix file `from_file`, would point to the `to_file`.
 fn create_path_expr(from_file: impl AsRef<Path>, to_file: impl AsRef<Path>) -> String {
     // FIXME: Clean these unwrap's up
@@ -0,0 +1,161 @@
+//! Structured output formats for [`NixpkgsProblem`]s.
+//!
+//! In addition to the human-readable `Display` output, problems can be rendered as plain JSON
+//! or as a SARIF 2.1.0 log, so that CI systems, editors, and bots can consume them without
+//! having to regex the prose output.
+
+use crate::nixpkgs_problem::NixpkgsProblem;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The `--format` CLI flag, selecting how problems are rendered on stdout.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The original human-prose output, one problem per paragraph.
+    #[default]
+    Human,
+    /// One JSON object per problem, newline-delimited.
+    Json,
+    /// A single SARIF 2.1.0 log, suitable for GitHub code-scanning annotations.
+    Sarif,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            "sarif" => Ok(Self::Sarif),
+            other => Err(format!("invalid output format \"{other}\", must be one of human, json, sarif")),
+        }
+    }
+}
+
+/// A single point (or span) in a source file that a [`NixpkgsProblem`] can be attributed to.
+#[derive(Clone, Serialize)]
+pub struct Location {
+    pub path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<Region>,
+}
+
+impl Location {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, region: None }
+    }
+
+    /// Attaches a line and column, as reported by `builtins.unsafeGetAttrPos` or similar.
+    ///
+    /// Every variant that carries position info reports both a line and a column, so `Region`
+    /// doesn't need to represent a line-only location; if a future variant only has a line,
+    /// add that case back rather than making `start_column` optional for everyone.
+    pub fn with_region(mut self, line: usize, column: usize) -> Self {
+        self.region = Some(Region { start_line: line, start_column: column });
+        self
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct Region {
+    pub start_line: usize,
+    pub start_column: usize,
+}
+
+/// A [`NixpkgsProblem`] rendered into a format-agnostic shape, ready to be serialized.
+#[derive(Serialize)]
+pub struct Diagnostic {
+    pub rule_id: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+}
+
+impl Diagnostic {
+    pub fn from_problem(problem: &NixpkgsProblem) -> Self {
+        Self {
+            rule_id: problem.rule_id(),
+            message: problem.to_string(),
+            location: problem.location(),
+        }
+    }
+}
+
+/// Renders all `problems` according to `format`, writing the result to `writer`.
+pub fn render(
+    problems: &[NixpkgsProblem],
+    format: OutputFormat,
+    writer: &mut impl std::io::Write,
+) -> std::io::Result<()> {
+    match format {
+        OutputFormat::Human => {
+            for problem in problems {
+                writeln!(writer, "- {problem}")?;
+            }
+            Ok(())
+        }
+        OutputFormat::Json => {
+            for problem in problems {
+                let diagnostic = Diagnostic::from_problem(problem);
+                let line = serde_json::to_string(&diagnostic)
+                    .expect("Diagnostic serialization is infallible");
+                writeln!(writer, "{line}")?;
+            }
+            Ok(())
+        }
+        OutputFormat::Sarif => {
+            let sarif = to_sarif(problems);
+            let json = serde_json::to_string_pretty(&sarif)
+                .expect("SARIF log serialization is infallible");
+            writeln!(writer, "{json}")
+        }
+    }
+}
+
+/// Builds a minimal SARIF 2.1.0 log (one run, one tool, one result per problem) as a
+/// [`serde_json::Value`], since we only ever need to emit it, never parse it back.
+fn to_sarif(problems: &[NixpkgsProblem]) -> serde_json::Value {
+    let results: Vec<serde_json::Value> = problems
+        .iter()
+        .map(|problem| {
+            let diagnostic = Diagnostic::from_problem(problem);
+            let locations = diagnostic
+                .location
+                .map(|location| {
+                    let mut physical_location = serde_json::json!({
+                        "artifactLocation": { "uri": location.path.display().to_string() },
+                    });
+                    if let Some(region) = location.region {
+                        physical_location["region"] = serde_json::json!({
+                            "startLine": region.start_line,
+                            "startColumn": region.start_column,
+                        });
+                    }
+                    vec![serde_json::json!({ "physicalLocation": physical_location })]
+                })
+                .unwrap_or_default();
+
+            serde_json::json!({
+                "ruleId": diagnostic.rule_id,
+                "message": { "text": diagnostic.message },
+                "locations": locations,
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "version": "2.1.0",
+        "$schema": "https://raw.githubusercontent.com/oasis-tcs/sarif-spec/main/Schemata/sarif-schema-2.1.0.json",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "nixpkgs-vet",
+                    "informationUri": "https://github.com/NixOS/nixpkgs/tree/master/pkgs/test/nixpkgs-vet",
+                }
+            },
+            "results": results,
+        }],
+    })
+}